@@ -1,317 +1,908 @@
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
-use dashmap::DashMap;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use tokio::time::{interval, Duration};
-use std::process::Command;
-
-/// Maximum number of entries allowed in the cache when memory usage is below threshold.
-const DEFAULT_MAX_ENTRIES: usize=100_000;
-
-/// Memory threshold (70% of system memory) to trigger more aggressive eviction.
-const MEMORY_THRESHOLD_PERCENT: usize=70;
-
-/// Each cache entry holds the value and a use_bit that tracks recent access.
-struct CacheEntry {
-    value: String,
-    /// The use_bit is updated on each access.
-    use_bit: AtomicBool,
-    /// When the entry was last accessed (monotonic counter).
-    last_access: AtomicUsize,
-}
-
-impl CacheEntry {
-    #[inline]
-    fn new(value: String, access_counter: usize)->Self {
-        CacheEntry {
-            value,
-            // New entries are marked as recently used.
-            use_bit: AtomicBool::new(true),
-            // Set the current access counter value
-            last_access: AtomicUsize::new(access_counter),
-        }
-    }
-}
-
-/// Our cache uses DashMap for concurrent access.
-#[derive(Clone)]
-struct Cache {
-    map: Arc<DashMap<String, CacheEntry>>,
-    access_counter: Arc<AtomicUsize>,
-}
-
-impl Cache {
-    fn new()->Self {
-        Cache {
-            map: Arc::new(DashMap::new()),
-            access_counter: Arc::new(AtomicUsize::new(0)),
-        }
-    }
-
-    /// Inserts or updates an entry.
-    #[inline]
-    fn put(&self, key: String, value: String) {
-        // Increment the access counter for each operation
-        let counter=self.access_counter.fetch_add(1, Ordering::SeqCst);
-        self.map.insert(key, CacheEntry::new(value, counter));
-    }
-
-    /// Retrieves an entry by key and marks it as recently used.
-    #[inline]
-    fn get(&self, key: &str)->Option<String> {
-        if let Some(entry)=self.map.get(key) {
-            // Mark as recently used
-            entry.use_bit.store(true, Ordering::Release);
-            
-            // Update the last access timestamp
-            let counter=self.access_counter.fetch_add(1, Ordering::SeqCst);
-            entry.last_access.store(counter, Ordering::Release);
-            
-            Some(entry.value.clone())
-        } else {
-            None
-        }
-    }
-
-    /// Get the current memory usage percentage
-    fn get_memory_usage_percent(&self)->usize {
-        // Try to read memory usage from procfs on Linux
-        if let Ok(output)=Command::new("sh")
-            .arg("-c")
-            .arg("free | grep Mem | awk '{print $3/$2 * 100}'")
-            .output() 
-        {
-            if let Ok(output_str)=String::from_utf8(output.stdout) {
-                if let Ok(value)=output_str.trim().parse::<f64>() {
-                    return value as usize;
-                }
-            }
-        }
-        
-        // Fallback: estimate memory usage based on cache size
-        // This is a very rough approximation
-        let entry_count=self.map.len();
-        let avg_key_size=32; // Assume average key size of 32 bytes
-        let avg_value_size=64; // Assume average value size of 64 bytes
-        let overhead=32; // Overhead per entry for metadata
-        
-        let estimated_memory=entry_count*(avg_key_size+avg_value_size+overhead);
-        
-        // Assuming 2GB RAM on t3.small (use u64 to avoid overflow)
-        let total_memory=2_u64*1024*1024*1024;
-        
-        // Calculate percentage
-        ((estimated_memory as f64/total_memory as f64)*100.0) as usize
-    }
-
-    /// Calculate the dynamic maximum entries based on memory usage
-    fn get_max_entries(&self)->usize {
-        let memory_usage=self.get_memory_usage_percent();
-        
-        if memory_usage<=MEMORY_THRESHOLD_PERCENT {
-            // If memory usage is below threshold, keep the default max
-            DEFAULT_MAX_ENTRIES
-        } else {
-            // Otherwise, gradually reduce max entries as memory usage increases
-            // At 100% memory usage, we'd allow only 20% of DEFAULT_MAX_ENTRIES
-            let reduction_factor=(100-memory_usage) as f64/(100-MEMORY_THRESHOLD_PERCENT) as f64;
-            // Ensure we don't reduce too aggressively
-            let reduction_factor=f64::max(0.2, reduction_factor);
-            
-            (DEFAULT_MAX_ENTRIES as f64*reduction_factor) as usize
-        }
-    }
-
-    /// Evicts entries using multi-tiered eviction strategy:
-    /// 1. First uses clock algorithm for normal eviction
-    /// 2. Falls back to LRU if needed to meet memory constraints
-    fn evict(&self) {
-        // Get current memory usage and determine max entries
-        let memory_usage=self.get_memory_usage_percent();
-        let current_max_entries=self.get_max_entries();
-        
-        // Skip eviction if under limits and memory is below threshold
-        if self.map.len()<=current_max_entries && memory_usage<MEMORY_THRESHOLD_PERCENT {
-            return;
-        }
-        
-        // Determine how many entries need to be evicted
-        let current_size=self.map.len();
-        let target_size=if memory_usage>=MEMORY_THRESHOLD_PERCENT {
-            // More aggressive eviction when memory pressure is high
-            current_max_entries
-        } else {
-            // Normal eviction to stay under entry limit
-            current_max_entries
-        };
-        
-        // Skip if nothing to evict
-        if current_size<=target_size {
-            return;
-        }
-        
-        let entries_to_evict=current_size-target_size;
-        
-        // Track entries marked for eviction in first pass
-        let mut to_evict=Vec::new();
-        
-        // First pass - use clock algorithm: reset use bits, mark unused entries
-        for entry in self.map.iter() {
-            if entry.use_bit.load(Ordering::Acquire) {
-                // Reset use bit
-                entry.use_bit.store(false, Ordering::Release);
-            } else {
-                // Entry wasn't used since last cycle, mark for eviction
-                to_evict.push(entry.key().clone());
-            }
-        }
-        
-        // If first pass didn't mark enough entries, do a second pass using LRU
-        if to_evict.len()<entries_to_evict && memory_usage>=MEMORY_THRESHOLD_PERCENT {
-            // Collect all entries with access times
-            let mut lru_candidates=Vec::new();
-            for entry in self.map.iter() {
-                // Skip entries already marked for eviction
-                if !to_evict.contains(entry.key()) {
-                    lru_candidates.push((
-                        entry.key().clone(),
-                        entry.last_access.load(Ordering::Acquire),
-                    ));
-                }
-            }
-            
-            // Sort by last access time (ascending)
-            lru_candidates.sort_by_key(|&(_, timestamp)| timestamp);
-            
-            // Take additional entries needed
-            let additional_needed=entries_to_evict-to_evict.len();
-            for (key, _) in lru_candidates.iter().take(additional_needed) {
-                to_evict.push(key.clone());
-            }
-        }
-        
-        // Print eviction stats for debugging
-        println!(
-            "Cache eviction: memory={}%, current_size={}, max_entries={}, evicting={}",
-            memory_usage, current_size, current_max_entries, to_evict.len()
-        );
-        
-        // Remove the entries marked for eviction
-        for key in to_evict {
-            self.map.remove(&key);
-        }
-    }
-}
-
-/// Request and response models for HTTP endpoints.
-#[derive(Deserialize)]
-struct PutRequest {
-    key: String,
-    value: String,
-}
-
-#[derive(Serialize)]
-struct ResponseMessage {
-    status: String,
-    message: String,
-}
-
-#[derive(Serialize)]
-struct GetResponse {
-    status: String,
-    key: String,
-    value: String,
-}
-
-/// HTTP handler for the PUT operation.
-#[post("/put")]
-async fn put_handler(cache: web::Data<Cache>, req: web::Json<PutRequest>)->impl Responder {
-    // Enforce maximum length for key and value (256 characters)
-    if req.key.len()>256 || req.value.len()>256 {
-        return HttpResponse::BadRequest().json(ResponseMessage {
-            status: "ERROR".into(),
-            message: "Key or Value exceeds 256 characters.".into(),
-        });
-    }
-    
-    // Check memory usage before adding new entry
-    let memory_usage=cache.get_memory_usage_percent();
-    if memory_usage>=95 {
-        // Run emergency eviction if memory is critically high
-        cache.evict();
-    }
-    
-    cache.put(req.key.clone(), req.value.clone());
-    HttpResponse::Ok().json(ResponseMessage {
-        status: "OK".into(),
-        message: "Key inserted/updated successfully.".into(),
-    })
-}
-
-/// HTTP handler for the GET operation.
-#[get("/get")]
-async fn get_handler(cache: web::Data<Cache>, query: web::Query<HashMap<String, String>>)->impl Responder {
-    let key=match query.get("key") {
-        Some(k)=>k,
-        None=> {
-            return HttpResponse::BadRequest().json(ResponseMessage {
-                status: "ERROR".into(),
-                message: "Missing key parameter.".into(),
-            })
-        }
-    };
-
-    if let Some(value)=cache.get(key) {
-        HttpResponse::Ok().json(GetResponse {
-            status: "OK".into(),
-            key: key.clone(),
-            value,
-        })
-    } else {
-        HttpResponse::NotFound().json(ResponseMessage {
-            status: "ERROR".into(),
-            message: "Key not found.".into(),
-        })
-    }
-}
-
-#[actix_web::main]
-async fn main()->std::io::Result<()> {
-    // Read environment variables
-    let workers=std::env::var("WORKERS")
-        .unwrap_or_else(|_| "2".to_string())
-        .parse::<usize>()
-        .unwrap_or(2);
-    
-    println!("Starting key-value cache service with {} workers", workers);
-    
-    // Initialize the shared cache.
-    let cache=Cache::new();
-
-    // Clone cache handle for the background eviction task.
-    let eviction_cache=cache.clone();
-
-    // Spawn a background task that periodically evicts entries.
-    tokio::spawn(async move {
-        // Set up an interval timer - check every second
-        let mut interval=interval(Duration::from_secs(1));
-        loop {
-            interval.tick().await;
-            eviction_cache.evict();
-        }
-    });
-
-    // Start the Actix Web server on port 7171.
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(cache.clone()))
-            .service(put_handler)
-            .service(get_handler)
-    })
-    .workers(workers)
-    .bind("0.0.0.0:7171")?
-    .run()
-    .await
-}
\ No newline at end of file
+use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::time::{interval, Duration};
+use std::time::Instant;
+
+/// Maximum number of entries allowed in the cache when memory usage is below threshold.
+const DEFAULT_MAX_ENTRIES: usize=100_000;
+
+/// Usage threshold (70% of the tracked-byte budget, `MAX_CACHE_BYTES`) to trigger more aggressive eviction.
+const MEMORY_THRESHOLD_PERCENT: usize=70;
+
+/// Fixed per-entry bookkeeping overhead (DashMap shard/bucket, atomics, etc.)
+/// added on top of a key's and value's own byte length.
+const ENTRY_OVERHEAD_BYTES: usize=48;
+
+/// Default byte budget for the cache's own tracked data, overridable via `MAX_CACHE_BYTES`.
+const DEFAULT_MAX_CACHE_BYTES: usize=512*1024*1024;
+
+/// Fraction of `current_max_entries` reserved for the S3-FIFO small queue `S`.
+const S3FIFO_SMALL_QUEUE_RATIO: f64=0.10;
+
+/// Number of independent hash functions (sketch rows) backing the TinyLFU admission filter.
+const TINYLFU_DEPTH: usize=4;
+
+/// Saturating ceiling for each sketch counter (fits a 4-bit counter).
+const TINYLFU_MAX_COUNT: u8=15;
+
+/// Default width (columns per row) of the TinyLFU count-min sketch.
+const DEFAULT_TINYLFU_WIDTH: usize=16_384;
+
+/// Default multiplier `k` in `max_scan = k * entries_to_evict`, bounding how many
+/// entries either eviction policy examines per `evict` call.
+const DEFAULT_EVICTION_SCAN_MULTIPLIER: usize=4;
+
+/// Hard ceiling on how long one background eviction cycle may run, overridable
+/// via `EVICTION_CYCLE_TIMEOUT_MS`.
+const DEFAULT_EVICTION_CYCLE_TIMEOUT_MS: u64=50;
+
+/// Number of recent `get` outcomes kept to compute the rolling hit ratio.
+const ROLLING_HIT_RATIO_WINDOW: usize=1_000;
+
+/// Hashes `key` with a per-row seed so each sketch row is an independent hash function.
+fn tinylfu_hash(seed: u64, key: &str)->u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher=std::collections::hash_map::DefaultHasher::new();
+    seed.hash(&mut hasher);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A count-min sketch of 4-bit saturating counters used to estimate key frequency
+/// for the optional TinyLFU admission filter.
+struct CountMinSketch {
+    width: usize,
+    rows: Vec<Vec<AtomicU8>>,
+    /// Total increments since the last halving, used to periodically decay counters.
+    total_increments: AtomicUsize,
+    reset_threshold: usize,
+}
+
+impl CountMinSketch {
+    fn new(width: usize, reset_threshold: usize)->Self {
+        let rows=(0..TINYLFU_DEPTH)
+            .map(|_| (0..width).map(|_| AtomicU8::new(0)).collect())
+            .collect();
+        CountMinSketch {
+            width,
+            rows,
+            total_increments: AtomicUsize::new(0),
+            reset_threshold,
+        }
+    }
+
+    fn slot(&self, row: usize, key: &str)->usize {
+        (tinylfu_hash(row as u64, key) as usize)%self.width
+    }
+
+    /// Bumps every row's counter for `key` (saturating), decaying the whole sketch
+    /// once enough increments have accumulated to keep estimates meaningful.
+    fn increment(&self, key: &str) {
+        for row in 0..TINYLFU_DEPTH {
+            let idx=self.slot(row, key);
+            let _=self.rows[row][idx].fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+                if v<TINYLFU_MAX_COUNT { Some(v+1) } else { None }
+            });
+        }
+        if self.total_increments.fetch_add(1, Ordering::Relaxed)+1>=self.reset_threshold {
+            self.halve();
+        }
+    }
+
+    /// Estimates `key`'s frequency as the minimum across all rows.
+    fn estimate(&self, key: &str)->u8 {
+        (0..TINYLFU_DEPTH)
+            .map(|row| self.rows[row][self.slot(row, key)].load(Ordering::Acquire))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn halve(&self) {
+        for row in self.rows.iter() {
+            for counter in row.iter() {
+                let _=counter.fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| Some(v/2));
+            }
+        }
+        self.total_increments.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Eviction strategy used by `Cache::evict`, selected once at startup.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EvictionPolicy {
+    /// Bounded, resumable CLOCK (second-chance) strategy; there is no LRU-sort fallback.
+    ClockLru,
+    /// S3-FIFO: small/main FIFO queues plus a ghost queue of evicted keys.
+    S3Fifo,
+}
+
+impl EvictionPolicy {
+    /// Reads `EVICTION_POLICY` from the environment; defaults to the existing clock/LRU strategy.
+    fn from_env()->Self {
+        match std::env::var("EVICTION_POLICY").as_deref() {
+            Ok("s3fifo")=>EvictionPolicy::S3Fifo,
+            _=>EvictionPolicy::ClockLru,
+        }
+    }
+}
+
+/// Each cache entry holds the value and a use_bit that tracks recent access.
+struct CacheEntry {
+    value: String,
+    /// The use_bit is updated on each access.
+    use_bit: AtomicBool,
+    /// When the entry was last accessed (monotonic counter).
+    last_access: AtomicUsize,
+    /// Saturating (0-3) access-frequency counter used by the S3-FIFO policy.
+    freq: AtomicU8,
+    /// Monotonic deadline after which this entry is treated as absent. `None` means no TTL.
+    expires_at: Option<Instant>,
+}
+
+impl CacheEntry {
+    #[inline]
+    fn new(value: String, access_counter: usize, expires_at: Option<Instant>)->Self {
+        CacheEntry {
+            value,
+            // New entries are marked as recently used.
+            use_bit: AtomicBool::new(true),
+            // Set the current access counter value
+            last_access: AtomicUsize::new(access_counter),
+            freq: AtomicU8::new(0),
+            expires_at,
+        }
+    }
+
+    /// Whether this entry's TTL deadline has passed.
+    #[inline]
+    fn is_expired(&self)->bool {
+        self.expires_at.map(|at| Instant::now()>=at).unwrap_or(false)
+    }
+}
+
+/// Bounded FIFO of evicted keys ("ghosts"). Membership is used to tell a
+/// returning key (promote straight to `M`) from a true one-hit wonder.
+struct GhostQueue {
+    order: Mutex<VecDeque<String>>,
+    set: dashmap::DashSet<String>,
+}
+
+impl GhostQueue {
+    fn new()->Self {
+        GhostQueue {
+            order: Mutex::new(VecDeque::new()),
+            set: dashmap::DashSet::new(),
+        }
+    }
+
+    fn contains(&self, key: &str)->bool {
+        self.set.contains(key)
+    }
+
+    /// Removes `key` from the ghost queue, e.g. when it returns to the cache.
+    fn remove(&self, key: &str) {
+        self.set.remove(key);
+        let mut order=self.order.lock().unwrap();
+        order.retain(|k| k!=key);
+    }
+
+    /// Pushes an evicted key, trimming the oldest ghosts past `capacity`.
+    fn push(&self, key: String, capacity: usize) {
+        let mut order=self.order.lock().unwrap();
+        if self.set.insert(key.clone()) {
+            order.push_back(key);
+        }
+        while order.len()>capacity {
+            if let Some(old)=order.pop_front() {
+                self.set.remove(&old);
+            }
+        }
+    }
+}
+
+/// Fixed-size ring of recent `get` outcomes (hit/miss), used to report a
+/// rolling hit ratio that reacts to policy or TTL changes without a restart.
+struct RollingHitRatio {
+    outcomes: Mutex<VecDeque<bool>>,
+}
+
+impl RollingHitRatio {
+    fn new()->Self {
+        RollingHitRatio {
+            outcomes: Mutex::new(VecDeque::with_capacity(ROLLING_HIT_RATIO_WINDOW)),
+        }
+    }
+
+    fn record(&self, hit: bool) {
+        let mut outcomes=self.outcomes.lock().unwrap();
+        outcomes.push_back(hit);
+        if outcomes.len()>ROLLING_HIT_RATIO_WINDOW {
+            outcomes.pop_front();
+        }
+    }
+
+    fn ratio(&self)->f64 {
+        let outcomes=self.outcomes.lock().unwrap();
+        if outcomes.is_empty() {
+            return 0.0;
+        }
+        let hits=outcomes.iter().filter(|&&hit| hit).count();
+        hits as f64/outcomes.len() as f64
+    }
+}
+
+/// Small/main/ghost FIFO queues backing the S3-FIFO eviction policy.
+struct S3FifoState {
+    small: Mutex<VecDeque<String>>,
+    main: Mutex<VecDeque<String>>,
+    ghost: GhostQueue,
+}
+
+impl S3FifoState {
+    fn new()->Self {
+        S3FifoState {
+            small: Mutex::new(VecDeque::new()),
+            main: Mutex::new(VecDeque::new()),
+            ghost: GhostQueue::new(),
+        }
+    }
+
+    /// Routes a newly-inserted key into `S`, or straight into `M` if it's a
+    /// returning ghost (it already proved itself once before).
+    fn insert(&self, key: String) {
+        if self.ghost.contains(&key) {
+            self.ghost.remove(&key);
+            self.main.lock().unwrap().push_back(key);
+        } else {
+            self.small.lock().unwrap().push_back(key);
+        }
+    }
+}
+
+/// Our cache uses DashMap for concurrent access.
+#[derive(Clone)]
+struct Cache {
+    map: Arc<DashMap<String, CacheEntry>>,
+    access_counter: Arc<AtomicUsize>,
+    policy: EvictionPolicy,
+    s3fifo: Arc<S3FifoState>,
+    /// Whether the optional TinyLFU admission filter gates `put`.
+    admission_enabled: bool,
+    sketch: Arc<CountMinSketch>,
+    /// TTL applied when a `put` omits `ttl_secs`, from `DEFAULT_TTL_SECS`.
+    default_ttl: Option<Duration>,
+    /// Persistent, resumable clock hand (FIFO order of keys) for `ClockLru` eviction.
+    clock_queue: Arc<Mutex<VecDeque<String>>>,
+    /// Running total of tracked bytes (keys + values + per-entry overhead) held by the cache.
+    tracked_bytes: Arc<AtomicUsize>,
+    /// Byte budget the tracked size is measured against, from `MAX_CACHE_BYTES`.
+    max_cache_bytes: usize,
+    /// Hard per-cycle timeout for `run_eviction_cycle`, from `EVICTION_CYCLE_TIMEOUT_MS`.
+    eviction_cycle_timeout: Duration,
+    /// Cumulative counters surfaced via `/stats`.
+    hits: Arc<AtomicUsize>,
+    misses: Arc<AtomicUsize>,
+    evictions: Arc<AtomicUsize>,
+    admission_rejections: Arc<AtomicUsize>,
+    rolling_hit_ratio: Arc<RollingHitRatio>,
+    /// Set once any `put` has ever produced an entry with an expiry, so
+    /// `sweep_expired` can skip its scan entirely for TTL-less workloads.
+    has_ttl_entries: Arc<AtomicBool>,
+    /// Process RSS at startup (before any entries), subtracted out of later RSS
+    /// readings so drift correction estimates the cache's own footprint rather
+    /// than the whole process's.
+    baseline_rss_bytes: usize,
+}
+
+impl Cache {
+    fn new()->Self {
+        let admission_enabled=std::env::var("ADMISSION_FILTER").as_deref()==Ok("tinylfu");
+        let sketch_width=std::env::var("TINYLFU_WIDTH")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_TINYLFU_WIDTH);
+        let default_ttl=std::env::var("DEFAULT_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let max_cache_bytes=std::env::var("MAX_CACHE_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_CACHE_BYTES);
+        let eviction_cycle_timeout=std::env::var("EVICTION_CYCLE_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| Duration::from_millis(DEFAULT_EVICTION_CYCLE_TIMEOUT_MS));
+        let baseline_rss_bytes=Self::read_rss_bytes().unwrap_or(0);
+
+        Cache {
+            map: Arc::new(DashMap::new()),
+            access_counter: Arc::new(AtomicUsize::new(0)),
+            policy: EvictionPolicy::from_env(),
+            s3fifo: Arc::new(S3FifoState::new()),
+            admission_enabled,
+            sketch: Arc::new(CountMinSketch::new(sketch_width, DEFAULT_MAX_ENTRIES*10)),
+            default_ttl,
+            clock_queue: Arc::new(Mutex::new(VecDeque::new())),
+            tracked_bytes: Arc::new(AtomicUsize::new(0)),
+            max_cache_bytes,
+            eviction_cycle_timeout,
+            hits: Arc::new(AtomicUsize::new(0)),
+            misses: Arc::new(AtomicUsize::new(0)),
+            evictions: Arc::new(AtomicUsize::new(0)),
+            admission_rejections: Arc::new(AtomicUsize::new(0)),
+            rolling_hit_ratio: Arc::new(RollingHitRatio::new()),
+            has_ttl_entries: Arc::new(AtomicBool::new(false)),
+            baseline_rss_bytes,
+        }
+    }
+
+    /// Removes `key` if present and accounts for the freed bytes. Returns whether
+    /// an entry was actually removed.
+    fn remove_entry(&self, key: &str)->bool {
+        if let Some((removed_key, entry))=self.map.remove(key) {
+            let size=removed_key.len()+entry.value.len()+ENTRY_OVERHEAD_BYTES;
+            let _=self.tracked_bytes.fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+                Some(v.saturating_sub(size))
+            });
+
+            // Also drop the key from whichever structure tracks live eviction
+            // order, in case the caller didn't already pop it itself (e.g. a
+            // TTL sweep or a victim displaced by the admission filter) — otherwise it would
+            // sit there forever since it never goes through capacity eviction.
+            match self.policy {
+                EvictionPolicy::ClockLru=> {
+                    let mut queue=self.clock_queue.lock().unwrap();
+                    if let Some(pos)=queue.iter().position(|k| k==key) {
+                        queue.remove(pos);
+                    }
+                }
+                EvictionPolicy::S3Fifo=> {
+                    let mut small=self.s3fifo.small.lock().unwrap();
+                    if let Some(pos)=small.iter().position(|k| k==key) {
+                        small.remove(pos);
+                    } else {
+                        drop(small);
+                        let mut main=self.s3fifo.main.lock().unwrap();
+                        if let Some(pos)=main.iter().position(|k| k==key) {
+                            main.remove(pos);
+                        }
+                    }
+                }
+            }
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Picks an eviction candidate by peeking the front of the queue that
+    /// policy's `evict` examines first (`clock_queue`, or `S` then `M`),
+    /// instead of scanning the whole map -- this runs inline on the `put`
+    /// request path, so it has to stay O(1) regardless of cache size. Use bits
+    /// and frequency counters are not consulted, so the candidate can differ
+    /// from the entry `evict` would actually remove.
+    fn select_eviction_victim(&self)->Option<String> {
+        match self.policy {
+            EvictionPolicy::ClockLru=>self.clock_queue.lock().unwrap().front().cloned(),
+            EvictionPolicy::S3Fifo=>self
+                .s3fifo
+                .small
+                .lock()
+                .unwrap()
+                .front()
+                .cloned()
+                .or_else(|| self.s3fifo.main.lock().unwrap().front().cloned()),
+        }
+    }
+
+    /// Inserts or updates an entry. When the TinyLFU admission filter is enabled
+    /// and the cache is full, a brand-new key is only admitted if it is estimated
+    /// to be at least as "hot" as the eviction victim it would displace.
+    /// `ttl_secs` overrides `DEFAULT_TTL_SECS`; `None` on both means no expiration.
+    #[inline]
+    fn put(&self, key: String, value: String, ttl_secs: Option<u64>) {
+        // Increment the access counter for each operation
+        let counter=self.access_counter.fetch_add(1, Ordering::SeqCst);
+        let is_new_key=!self.map.contains_key(&key);
+
+        if self.admission_enabled {
+            self.sketch.increment(&key);
+
+            if is_new_key && self.map.len()>=self.get_max_entries() {
+                if let Some(victim_key)=self.select_eviction_victim() {
+                    let incoming_freq=self.sketch.estimate(&key);
+                    let victim_freq=self.sketch.estimate(&victim_key);
+                    if incoming_freq<victim_freq {
+                        // Newcomer looks cold next to the victim; drop it and
+                        // leave the existing entry in place.
+                        self.admission_rejections.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    self.remove_entry(&victim_key);
+                }
+            }
+        }
+
+        let ttl=ttl_secs.map(Duration::from_secs).or(self.default_ttl);
+        let expires_at=ttl.map(|d| Instant::now()+d);
+        if expires_at.is_some() {
+            self.has_ttl_entries.store(true, Ordering::Relaxed);
+        }
+
+        // Account for the byte footprint: drop the old value's size on overwrite,
+        // then add the new entry's size.
+        let old_size=self.map.get(&key).map(|e| key.len()+e.value.len()+ENTRY_OVERHEAD_BYTES);
+        let new_size=key.len()+value.len()+ENTRY_OVERHEAD_BYTES;
+        self.map.insert(key.clone(), CacheEntry::new(value, counter, expires_at));
+        if let Some(old_size)=old_size {
+            let _=self.tracked_bytes.fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+                Some(v.saturating_sub(old_size))
+            });
+        }
+        self.tracked_bytes.fetch_add(new_size, Ordering::AcqRel);
+
+        if is_new_key {
+            match self.policy {
+                EvictionPolicy::S3Fifo=>self.s3fifo.insert(key),
+                EvictionPolicy::ClockLru=>self.clock_queue.lock().unwrap().push_back(key),
+            }
+        }
+    }
+
+    /// Retrieves an entry by key and marks it as recently used. An entry past
+    /// its TTL deadline is treated as absent and removed lazily.
+    #[inline]
+    fn get(&self, key: &str)->Option<String> {
+        if self.admission_enabled {
+            self.sketch.increment(key);
+        }
+
+        if let Some(entry)=self.map.get(key) {
+            if entry.is_expired() {
+                drop(entry);
+                self.remove_entry(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.rolling_hit_ratio.record(false);
+                return None;
+            }
+
+            // Mark as recently used
+            entry.use_bit.store(true, Ordering::Release);
+
+            // Update the last access timestamp
+            let counter=self.access_counter.fetch_add(1, Ordering::SeqCst);
+            entry.last_access.store(counter, Ordering::Release);
+
+            // Saturating frequency bump (0-3), consulted by the S3-FIFO policy.
+            let _=entry.freq.fetch_update(Ordering::AcqRel, Ordering::Acquire, |f| {
+                if f<3 { Some(f+1) } else { None }
+            });
+
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.rolling_hit_ratio.record(true);
+            Some(entry.value.clone())
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            self.rolling_hit_ratio.record(false);
+            None
+        }
+    }
+
+    /// Sweeps and drops all entries past their TTL deadline. Run by the
+    /// background task once per tick, before its eviction cycle.
+    /// Skips the full-map scan entirely when no `put` has ever set an expiry,
+    /// so TTL-less workloads don't pay O(n) per tick for nothing.
+    fn sweep_expired(&self) {
+        if !self.has_ttl_entries.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let expired: Vec<String>=self
+            .map
+            .iter()
+            .filter(|entry| entry.is_expired())
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for key in expired {
+            self.remove_entry(&key);
+        }
+    }
+
+    /// Reads this process's resident set size from `/proc/self/statm`, with no
+    /// subprocess involved. Returns `None` off Linux or if the read fails.
+    fn read_rss_bytes()->Option<usize> {
+        let contents=std::fs::read_to_string("/proc/self/statm").ok()?;
+        let resident_pages: usize=contents.split_whitespace().nth(1)?.parse().ok()?;
+        // 4 KiB pages on every Linux platform we target; good enough for a drift check.
+        const PAGE_SIZE_BYTES: usize=4096;
+        Some(resident_pages*PAGE_SIZE_BYTES)
+    }
+
+    /// Cross-checks the self-accounted tracked size against a real RSS reading,
+    /// correcting for drift (e.g. fragmentation, accounting bugs) without letting
+    /// whole-process memory (binary, runtime, connections) leak into the budget.
+    /// `baseline_rss_bytes` nets out everything that isn't cache data. Unlike a
+    /// one-way ratchet, this replaces `tracked_bytes` with the fresh estimate in
+    /// either direction, so memory freed by an eviction batch (or by the OS
+    /// reclaiming fragmented pages) is reflected instead of leaving a permanent
+    /// phantom surplus. Called once per eviction batch, not on every request.
+    fn correct_drift_from_rss(&self) {
+        if let Some(rss_bytes)=Self::read_rss_bytes() {
+            let cache_rss_estimate=rss_bytes.saturating_sub(self.baseline_rss_bytes);
+            self.tracked_bytes.store(cache_rss_estimate, Ordering::Release);
+        }
+    }
+
+    /// Get the current memory usage percentage
+    fn get_memory_usage_percent(&self)->usize {
+        let tracked_bytes=self.tracked_bytes.load(Ordering::Acquire);
+        ((tracked_bytes as f64/self.max_cache_bytes as f64)*100.0) as usize
+    }
+
+    /// Calculate the dynamic maximum entries based on memory usage
+    fn get_max_entries(&self)->usize {
+        // Tracked bytes can overshoot `max_cache_bytes` between eviction passes
+        // (e.g. a burst of puts before the next cycle runs), so clamp to 100
+        // -- otherwise `100-memory_usage` underflows and wraps this usize.
+        let memory_usage=std::cmp::min(self.get_memory_usage_percent(), 100);
+
+        if memory_usage<=MEMORY_THRESHOLD_PERCENT {
+            // If memory usage is below threshold, keep the default max
+            DEFAULT_MAX_ENTRIES
+        } else {
+            // Otherwise, gradually reduce max entries as memory usage increases
+            // At 100% memory usage, we'd allow only 20% of DEFAULT_MAX_ENTRIES
+            let reduction_factor=(100-memory_usage) as f64/(100-MEMORY_THRESHOLD_PERCENT) as f64;
+            // Ensure we don't reduce too aggressively
+            let reduction_factor=f64::max(0.2, reduction_factor);
+
+            (DEFAULT_MAX_ENTRIES as f64*reduction_factor) as usize
+        }
+    }
+
+    /// Evicts entries down to the current budget, using whichever policy was
+    /// selected via `EVICTION_POLICY` at startup.
+    fn evict(&self) {
+        match self.policy {
+            EvictionPolicy::ClockLru=>self.evict_clock_lru(),
+            EvictionPolicy::S3Fifo=>self.evict_s3fifo(),
+        }
+    }
+
+    /// Runs `evict` batch-by-batch, re-measuring real usage against RSS before
+    /// each one, until the cache is back under budget or `eviction_cycle_timeout`
+    /// elapses first. Guards against accounting and reality diverging (e.g.
+    /// memory rising faster than entry counts suggest, or a batch freeing more
+    /// than `tracked_bytes` expected) by never trusting a single stale reading.
+    fn run_eviction_cycle(&self) {
+        let start=Instant::now();
+
+        loop {
+            self.correct_drift_from_rss();
+            self.evict();
+
+            let memory_usage=self.get_memory_usage_percent();
+            let over_budget=memory_usage>=MEMORY_THRESHOLD_PERCENT || self.map.len()>self.get_max_entries();
+            if !over_budget {
+                break;
+            }
+
+            if start.elapsed()>=self.eviction_cycle_timeout {
+                println!(
+                    "WARNING: eviction cycle timed out after {:?} while still over threshold (memory={}%, entries={})",
+                    self.eviction_cycle_timeout, memory_usage, self.map.len()
+                );
+                break;
+            }
+        }
+    }
+
+    /// Evicts entries using a bounded, resumable CLOCK hand: examines at most
+    /// `max_scan = k * entries_to_evict` entries per call and stops (rather than
+    /// falling through to a full LRU sort) if the budget runs out first. The
+    /// clock hand's position is the FIFO order of `clock_queue`, which persists
+    /// across calls so the next tick resumes where this one left off.
+    fn evict_clock_lru(&self) {
+        // Get current memory usage and determine max entries
+        let memory_usage=self.get_memory_usage_percent();
+        let current_max_entries=self.get_max_entries();
+
+        // Skip eviction if under limits and memory is below threshold
+        if self.map.len()<=current_max_entries && memory_usage<MEMORY_THRESHOLD_PERCENT {
+            return;
+        }
+
+        // Determine how many entries need to be evicted
+        let current_size=self.map.len();
+        if current_size<=current_max_entries {
+            return;
+        }
+
+        let entries_to_evict=current_size-current_max_entries;
+        let max_scan=std::cmp::max(entries_to_evict, entries_to_evict*DEFAULT_EVICTION_SCAN_MULTIPLIER);
+
+        let mut queue=self.clock_queue.lock().unwrap();
+        let mut evicted=0;
+        let mut scanned=0;
+
+        while evicted<entries_to_evict && scanned<max_scan {
+            let key=match queue.pop_front() {
+                Some(k)=>k,
+                None=>break, // Nothing left in the ring this cycle.
+            };
+            scanned+=1;
+
+            let entry=match self.map.get(&key) {
+                Some(e)=>e,
+                None=>continue, // Already gone (e.g. TTL sweep); drop from the ring.
+            };
+
+            if entry.use_bit.load(Ordering::Acquire) {
+                // Give it a second chance: clear the bit and move it to the back.
+                entry.use_bit.store(false, Ordering::Release);
+                drop(entry);
+                queue.push_back(key);
+            } else {
+                drop(entry);
+                self.remove_entry(&key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                evicted+=1;
+            }
+        }
+        drop(queue);
+
+        // Print eviction stats for debugging
+        println!(
+            "Cache eviction: memory={}%, current_size={}, max_entries={}, evicted={}, scanned={}/{}",
+            memory_usage, current_size, current_max_entries, evicted, scanned, max_scan
+        );
+
+        // If the scan budget ran out before freeing enough entries, we simply stop
+        // here; the clock hand resumes from the same position on the next tick.
+    }
+
+    /// Evicts entries using the S3-FIFO policy: drain `S` (promoting warm
+    /// entries to `M`, demoting cold ones to the ghost queue) and then drain
+    /// `M` with second-chance re-insertion, until each queue is back under its
+    /// own quota. This is deliberately judged against `S`/`M`'s own caps, not
+    /// the global entry count -- `S` drifting over `small_cap` is what drives
+    /// promotion/demotion in steady-state S3-FIFO, and that has to keep
+    /// happening even when the cache as a whole is comfortably under budget.
+    /// Bounded by `max_scan = k * entries_over_quota` (shared across both
+    /// queues, so a hot main queue that keeps giving itself second chances
+    /// can't spin the call indefinitely); like the clock hand, whatever's left
+    /// over just resumes from the same queue positions next tick.
+    fn evict_s3fifo(&self) {
+        let memory_usage=self.get_memory_usage_percent();
+        let current_max_entries=self.get_max_entries();
+
+        let small_cap=std::cmp::max(1, (current_max_entries as f64*S3FIFO_SMALL_QUEUE_RATIO) as usize);
+        let main_cap=current_max_entries.saturating_sub(small_cap);
+
+        let excess_small=self.s3fifo.small.lock().unwrap().len().saturating_sub(small_cap);
+        let excess_main=self.s3fifo.main.lock().unwrap().len().saturating_sub(main_cap);
+        if excess_small==0 && excess_main==0 {
+            return;
+        }
+
+        let entries_over_quota=excess_small+excess_main;
+        let max_scan=std::cmp::max(entries_over_quota, entries_over_quota*DEFAULT_EVICTION_SCAN_MULTIPLIER);
+
+        let before=self.map.len();
+        let mut scanned=0;
+
+        // Drain S down to its quota, within the scan budget.
+        while scanned<max_scan {
+            let over_quota=self.s3fifo.small.lock().unwrap().len()>small_cap;
+            if !over_quota {
+                break;
+            }
+            let key=match self.s3fifo.small.lock().unwrap().pop_front() {
+                Some(k)=>k,
+                None=>break,
+            };
+            scanned+=1;
+            let freq=match self.map.get(&key) {
+                Some(entry)=>entry.freq.load(Ordering::Acquire),
+                None=>continue, // Already removed elsewhere; drop from the queue.
+            };
+            if freq>1 {
+                self.s3fifo.main.lock().unwrap().push_back(key);
+            } else {
+                self.remove_entry(&key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                self.s3fifo.ghost.push(key, small_cap);
+            }
+        }
+
+        // Drain M down to its quota, giving warm entries a second chance, within
+        // whatever scan budget S didn't use.
+        while scanned<max_scan {
+            let over_quota=self.s3fifo.main.lock().unwrap().len()>main_cap;
+            if !over_quota {
+                break;
+            }
+            let key=match self.s3fifo.main.lock().unwrap().pop_front() {
+                Some(k)=>k,
+                None=>break,
+            };
+            scanned+=1;
+            let freq=match self.map.get(&key) {
+                Some(entry)=>entry.freq.load(Ordering::Acquire),
+                None=>continue,
+            };
+            if freq>0 {
+                if let Some(entry)=self.map.get(&key) {
+                    entry.freq.store(freq-1, Ordering::Release);
+                }
+                self.s3fifo.main.lock().unwrap().push_back(key);
+            } else {
+                self.remove_entry(&key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        println!(
+            "Cache eviction (s3fifo): memory={}%, before={}, after={}, max_entries={}, scanned={}/{}",
+            memory_usage, before, self.map.len(), current_max_entries, scanned, max_scan
+        );
+    }
+}
+
+/// Request and response models for HTTP endpoints.
+#[derive(Deserialize)]
+struct PutRequest {
+    key: String,
+    value: String,
+    /// Optional per-entry TTL in seconds; falls back to `DEFAULT_TTL_SECS` when omitted.
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ResponseMessage {
+    status: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct GetResponse {
+    status: String,
+    key: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct StatsResponse {
+    entries: usize,
+    tracked_bytes: usize,
+    max_entries: usize,
+    memory_usage_percent: usize,
+    hits: usize,
+    misses: usize,
+    evictions: usize,
+    admission_rejections: usize,
+    /// Hit ratio over the last `ROLLING_HIT_RATIO_WINDOW` `get` calls.
+    rolling_hit_ratio: f64,
+}
+
+/// HTTP handler for the PUT operation.
+#[post("/put")]
+async fn put_handler(cache: web::Data<Cache>, req: web::Json<PutRequest>)->impl Responder {
+    // Enforce maximum length for key and value (256 characters)
+    if req.key.len()>256 || req.value.len()>256 {
+        return HttpResponse::BadRequest().json(ResponseMessage {
+            status: "ERROR".into(),
+            message: "Key or Value exceeds 256 characters.".into(),
+        });
+    }
+
+    // Check memory usage before adding new entry
+    let memory_usage=cache.get_memory_usage_percent();
+    if memory_usage>=95 {
+        // Run emergency eviction if memory is critically high. This is its own
+        // one-off cycle, so it gets its own single drift correction.
+        cache.correct_drift_from_rss();
+        cache.evict();
+    }
+
+    cache.put(req.key.clone(), req.value.clone(), req.ttl_secs);
+    HttpResponse::Ok().json(ResponseMessage {
+        status: "OK".into(),
+        message: "Key inserted/updated successfully.".into(),
+    })
+}
+
+/// HTTP handler for the GET operation.
+#[get("/get")]
+async fn get_handler(cache: web::Data<Cache>, query: web::Query<HashMap<String, String>>)->impl Responder {
+    let key=match query.get("key") {
+        Some(k)=>k,
+        None=> {
+            return HttpResponse::BadRequest().json(ResponseMessage {
+                status: "ERROR".into(),
+                message: "Missing key parameter.".into(),
+            })
+        }
+    };
+
+    if let Some(value)=cache.get(key) {
+        HttpResponse::Ok().json(GetResponse {
+            status: "OK".into(),
+            key: key.clone(),
+            value,
+        })
+    } else {
+        HttpResponse::NotFound().json(ResponseMessage {
+            status: "ERROR".into(),
+            message: "Key not found.".into(),
+        })
+    }
+}
+
+/// HTTP handler exposing cache statistics, including a rolling hit ratio, so
+/// eviction/admission policy changes can be compared empirically on real traffic.
+#[get("/stats")]
+async fn stats_handler(cache: web::Data<Cache>)->impl Responder {
+    HttpResponse::Ok().json(StatsResponse {
+        entries: cache.map.len(),
+        tracked_bytes: cache.tracked_bytes.load(Ordering::Acquire),
+        max_entries: cache.get_max_entries(),
+        memory_usage_percent: cache.get_memory_usage_percent(),
+        hits: cache.hits.load(Ordering::Acquire),
+        misses: cache.misses.load(Ordering::Acquire),
+        evictions: cache.evictions.load(Ordering::Acquire),
+        admission_rejections: cache.admission_rejections.load(Ordering::Acquire),
+        rolling_hit_ratio: cache.rolling_hit_ratio.ratio(),
+    })
+}
+
+#[actix_web::main]
+async fn main()->std::io::Result<()> {
+    // Read environment variables
+    let workers=std::env::var("WORKERS")
+        .unwrap_or_else(|_| "2".to_string())
+        .parse::<usize>()
+        .unwrap_or(2);
+
+    println!("Starting key-value cache service with {} workers", workers);
+
+    // Initialize the shared cache.
+    let cache=Cache::new();
+
+    // Clone cache handle for the background eviction task.
+    let eviction_cache=cache.clone();
+
+    // Spawn a background task that periodically evicts entries.
+    tokio::spawn(async move {
+        // Set up an interval timer - check every second
+        let mut interval=interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            // Drop expired entries before running the cross-checked eviction cycle.
+            eviction_cache.sweep_expired();
+            eviction_cache.run_eviction_cycle();
+        }
+    });
+
+    // Start the Actix Web server on port 7171.
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(cache.clone()))
+            .service(put_handler)
+            .service(get_handler)
+            .service(stats_handler)
+    })
+    .workers(workers)
+    .bind("0.0.0.0:7171")?
+    .run()
+    .await
+}